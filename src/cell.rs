@@ -0,0 +1,154 @@
+//! Typed, `Pod`-backed access to the persistent region.
+
+use core::marker::PhantomData;
+use core::mem::{align_of, size_of};
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::{PersistentBuff, MAGIC_NUMBER};
+
+/// A typed view over the persistent buff, treating its payload as a single `T`.
+///
+/// Builds on [`PersistentBuff::take_raw`], replacing the hand-rolled byte
+/// (de)serialization most callers would otherwise need with direct `&T`/`&mut T`
+/// access. Since the linker can't guarantee the reserved region is aligned for an
+/// arbitrary `T`, alignment is checked once at construction rather than on every
+/// access.
+pub struct PersistentCell<T> {
+    magic: *mut u32,
+    cell: *mut T,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Pod + Zeroable> PersistentCell<T> {
+    /// Take a managed, typed view of the persistent buff.
+    ///
+    /// Returns `None` if the region was already taken, is too small to hold a `T`,
+    /// or isn't aligned for `T`.
+    pub fn take_managed() -> Option<Self> {
+        PersistentBuff::take_raw().and_then(Self::from_raw)
+    }
+
+    /// Steal a managed, typed view of the persistent buff without check.
+    /// See [Self::take_managed].
+    ///
+    /// # Safety
+    /// Calling this function could allow to have two mutable reference to the same buffer.
+    /// Make sure to only have one reference at a time to avoid multiple mutable reference.
+    pub unsafe fn steal_managed() -> Option<Self> {
+        Self::from_raw(PersistentBuff::steal())
+    }
+
+    fn from_raw(b: &'static mut [u8]) -> Option<Self> {
+        if b.len() < size_of::<u32>() {
+            return None;
+        }
+        let magic = b.as_mut_ptr().cast::<u32>();
+        let data = &mut b[size_of::<u32>()..];
+        if data.len() < size_of::<T>() {
+            return None;
+        }
+        let cell = data.as_mut_ptr().cast::<T>();
+        if !(cell as usize).is_multiple_of(align_of::<T>()) {
+            return None;
+        }
+        Some(Self {
+            magic,
+            cell,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Mark the cell as valid.
+    fn mark(&mut self) {
+        unsafe {
+            self.magic.write_unaligned(MAGIC_NUMBER);
+        }
+    }
+
+    /// Unmark the cell.
+    fn unmark(&mut self) {
+        unsafe {
+            self.magic.write_unaligned(0);
+        }
+    }
+
+    /// Verify if the cell has valid data in it.
+    pub fn valid(&self) -> bool {
+        unsafe { self.magic.read_unaligned() == MAGIC_NUMBER }
+    }
+
+    /// Get `&T` if the cell is valid, if not, return None.
+    pub fn get(&self) -> Option<&T> {
+        if self.valid() {
+            Some(unsafe { &*self.cell })
+        } else {
+            None
+        }
+    }
+
+    /// Get `&mut T` if the cell is valid, if not, return None.
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        if self.valid() {
+            Some(unsafe { &mut *self.cell })
+        } else {
+            None
+        }
+    }
+
+    /// Check if the cell is valid, if not call the provided closure (e.g. `|t| *t = T::zeroed()`).
+    /// Then mark the cell as valid.
+    pub fn validate<F>(&mut self, f: F) -> &mut T
+    where
+        F: FnOnce(&mut T),
+    {
+        if !self.valid() {
+            f(unsafe { &mut *self.cell });
+        }
+        self.mark();
+        unsafe { &mut *self.cell }
+    }
+
+    /// Force reset the cell to a known state via the closure and mark as valid.
+    pub fn reset<F>(&mut self, f: F) -> &mut T
+    where
+        F: FnOnce(&mut T),
+    {
+        f(unsafe { &mut *self.cell });
+        self.mark();
+        unsafe { &mut *self.cell }
+    }
+
+    /// Mark the cell as invalid.
+    pub fn invalidate(&mut self) {
+        self.unmark();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leak_buff(len: usize) -> &'static mut [u8] {
+        Box::leak(vec![0u8; len].into_boxed_slice())
+    }
+
+    #[test]
+    fn region_too_small_for_magic_returns_none_instead_of_panicking() {
+        assert!(PersistentCell::<u32>::from_raw(leak_buff(3)).is_none());
+    }
+
+    #[test]
+    fn region_too_small_for_payload_returns_none() {
+        assert!(PersistentCell::<u32>::from_raw(leak_buff(size_of::<u32>())).is_none());
+    }
+
+    #[test]
+    fn valid_region_round_trips_through_validate() {
+        let mut cell = PersistentCell::<u32>::from_raw(leak_buff(64)).unwrap();
+        assert!(!cell.valid());
+        *cell.validate(|v| *v = 42) += 1;
+        assert!(cell.valid());
+        assert_eq!(*cell.get().unwrap(), 43);
+    }
+}
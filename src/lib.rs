@@ -54,6 +54,15 @@
 //! }
 //! ```
 //!
+//! ## Features
+//! - `thread-safe`: claim the buffer through [`critical_section::with`](https://docs.rs/critical-section)
+//!   instead of a bare atomic swap, so `take_raw`/`take_managed` stay sound on multi-core targets
+//!   and when called from an interrupt. Off by default, since most targets are single-core and the
+//!   extra synchronization isn't free.
+//! - `zeroize`: make `invalidate()` clear the magic and then scrub the payload with a
+//!   volatile write loop, instead of just clearing the magic. `take_zeroize()` and
+//!   `validate_scrubbed()` always scrub regardless of this feature.
+//!
 //! ## License
 //! Licensed under either of
 //! - Apache License, Version 2.0 ([LICENSE-APACHE](LICENSE-APACHE) or
@@ -66,14 +75,39 @@
 //! ## Contribution
 //! Unless you explicitly state otherwise, any contribution intentionally submitted for inclusion in the work by you, as defined in the Apache-2.0 license, shall be dual licensed as above, without any additional terms or conditions.
 
-#![no_std]
-#![no_main]
+#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(test), no_main)]
 #![deny(missing_docs)]
 
+mod cell;
+mod crc;
+mod partition;
+mod sync;
+mod versioned;
+mod zeroize;
+
 use core::sync::atomic::{AtomicBool, Ordering};
 
+use crc::crc32;
+use sync::critical_section;
+use zeroize::scrub;
+
+pub use cell::PersistentCell;
+pub use partition::{PersistentPartition, PersistentPartitions};
+pub use versioned::PersistentBuffVersioned;
+
 const MAGIC_NUMBER: u32 = 0xFAB42069;
-static mut PERSISTENT_BUFF_TAKEN: AtomicBool = AtomicBool::new(false);
+static PERSISTENT_BUFF_TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// Atomically claim `flag`, returning `true` if this call is the one that flipped it
+/// from unclaimed to claimed.
+///
+/// Under the `thread-safe` feature this runs inside a [`critical_section`], making
+/// the claim indivisible across interrupts and cores rather than relying on the
+/// atomicity of the swap alone.
+fn try_claim(flag: &AtomicBool) -> bool {
+    critical_section(|| !flag.swap(true, Ordering::Relaxed))
+}
 
 /// Strut to request the persistent buff and manage it somewhat "safely".
 /// When acquiring the buffer you need to validate/init it to a known sate.
@@ -109,12 +143,10 @@ impl PersistentBuff {
 
     /// Get the raw persistent slice.
     pub fn take_raw() -> Option<&'static mut [u8]> {
-        unsafe {
-            if PERSISTENT_BUFF_TAKEN.swap(true, Ordering::Relaxed) {
-                None
-            } else {
-                Some(Self::steal())
-            }
+        if try_claim(&PERSISTENT_BUFF_TAKEN) {
+            Some(unsafe { Self::steal() })
+        } else {
+            None
         }
     }
 
@@ -165,6 +197,19 @@ impl PersistentBuff {
         }
     }
 
+    /// Consume the buffer, clearing the magic before overwriting its payload with a
+    /// volatile, non-elidable write loop.
+    ///
+    /// The magic is cleared first so a reset landing mid-scrub can never be read back
+    /// as a "valid" all-zero buffer next boot. Use this instead of [`Self::invalidate`]
+    /// when the payload holds short-lived secrets (session keys, handshake nonces)
+    /// that must not survive into the next boot or leak to whatever reuses the region
+    /// next.
+    pub fn take_zeroize(mut self) {
+        self.unmark();
+        scrub(self.buff);
+    }
+
     /// Force to reset the buffer to a known state via the closure and mark as valid for next boot then
     /// takes the static buff from the managed buff
     pub fn take_reset<F>(mut self, f: F) -> &'static mut [u8]
@@ -224,8 +269,365 @@ impl PersistentBuff {
         self.buff
     }
 
+    /// Check if the buffer is valid, if not, overwrite any residual bytes with a
+    /// volatile, non-elidable write loop before calling the provided closure.
+    /// Then mark the buffer as valid.
+    ///
+    /// Unlike [`Self::validate`], this guarantees residual RAM contents from a
+    /// previous owner of the region never leak into the freshly validated buffer.
+    pub fn validate_scrubbed<F>(&mut self, f: F) -> &mut [u8]
+    where
+        F: FnOnce(&mut [u8]),
+    {
+        if !self.valid() {
+            scrub(self.buff);
+            f(self.buff)
+        }
+        self.mark();
+        self.buff
+    }
+
+    /// Mark the buffer as invalid.
+    ///
+    /// Under the `zeroize` feature, the magic is cleared first and the payload is then
+    /// overwritten with a volatile, non-elidable write loop — clearing the magic first
+    /// means a reset landing mid-scrub can never be read back as a "valid" all-zero
+    /// buffer next boot. See [`Self::take_zeroize`] to get the same guarantee while
+    /// also consuming the buffer.
+    pub fn invalidate(&mut self) {
+        self.unmark();
+        #[cfg(feature = "zeroize")]
+        scrub(self.buff);
+    }
+}
+
+/// A guard around the payload of a [`PersistentBuffChecked`].
+///
+/// Mutations through [`core::ops::DerefMut`] are only safe to trust across a
+/// reboot once the CRC stored alongside the magic has been refreshed. Rather
+/// than relying on the caller to remember to re-mark, the guard recomputes
+/// and writes the CRC (and re-stamps the magic) when it is dropped.
+pub struct CheckedGuard<'a> {
+    magic: *mut u32,
+    crc: *mut u32,
+    buff: &'a mut [u8],
+}
+
+impl<'a> core::ops::Deref for CheckedGuard<'a> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.buff
+    }
+}
+
+impl<'a> core::ops::DerefMut for CheckedGuard<'a> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        self.buff
+    }
+}
+
+impl<'a> Drop for CheckedGuard<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            self.crc.write_unaligned(crc32(self.buff));
+            self.magic.write_unaligned(MAGIC_NUMBER);
+        }
+    }
+}
+
+/// Strut to request the persistent buff with CRC32 integrity checking.
+///
+/// Compared to [`PersistentBuff`], this reserves an extra `u32` after the
+/// magic to store a CRC32 of the payload, so a buffer that was only
+/// partially written during a brown-out is reported invalid instead of
+/// silently passing the magic-only check.
+pub struct PersistentBuffChecked {
+    magic: *mut u32,
+    crc: *mut u32,
+    buff: &'static mut [u8],
+}
+
+impl PersistentBuffChecked {
+    /// Take a checked version of the persistent buff.
+    /// Allows to check if the buffer is valid or not, including integrity of the payload, before usage.
+    /// Note that compared to [`PersistentBuff::take_managed`], you will lose an extra 4 bytes for storage of the CRC.
+    ///
+    /// Returns `None` if the region was already taken, or if it is too small to hold
+    /// the magic and CRC header.
+    pub fn take_checked() -> Option<Self> {
+        PersistentBuff::take_raw().and_then(Self::from_raw)
+    }
+
+    /// Steal a checked version of the persistent buff without check.
+    /// See [Self::take_checked]
+    ///
+    /// # Safety
+    /// Calling this function could allow to have two mutable reference to the same buffer.
+    /// Make sure to only have one reference at a time to avoid multiple mutable reference.
+    pub unsafe fn steal_checked() -> Option<Self> {
+        Self::from_raw(PersistentBuff::steal())
+    }
+
+    fn from_raw(b: &'static mut [u8]) -> Option<Self> {
+        if b.len() < 2 * core::mem::size_of::<u32>() {
+            return None;
+        }
+        let magic = b.as_mut_ptr().cast::<u32>();
+        // SAFETY: `b` is at least 2 * size_of::<u32>() long, checked above.
+        let crc = unsafe { magic.add(1) };
+        Some(Self {
+            magic,
+            crc,
+            buff: &mut b[2 * core::mem::size_of::<u32>()..],
+        })
+    }
+
+    /// Mark the persistent buffer with valid data in it, refreshing the CRC.
+    fn mark(&mut self) {
+        unsafe {
+            self.crc.write_unaligned(crc32(self.buff));
+            self.magic.write_unaligned(MAGIC_NUMBER);
+        }
+    }
+
+    /// Unmark the persistent buffer with valid data in it.
+    fn unmark(&mut self) {
+        unsafe {
+            self.magic.write_unaligned(0);
+        }
+    }
+
+    /// Verify if the persistent buffer has valid data in it, i.e. the magic is set
+    /// and the stored CRC32 matches the current payload.
+    pub fn valid(&self) -> bool {
+        unsafe {
+            self.magic.read_unaligned() == MAGIC_NUMBER
+                && self.crc.read_unaligned() == crc32(self.buff)
+        }
+    }
+
+    /// Take the static internal buffer from the checked buff if valid
+    pub fn take(self) -> Option<&'static mut [u8]> {
+        if self.valid() {
+            return Some(self.buff);
+        } else {
+            return None;
+        }
+    }
+
+    /// Force to reset the buffer to a known state via the closure and mark as valid for next boot then
+    /// takes a [`CheckedGuard`] that refreshes the CRC on drop.
+    pub fn take_reset<F>(mut self, f: F) -> CheckedGuard<'static>
+    where
+        F: FnOnce(&mut [u8]),
+    {
+        f(self.buff);
+        self.mark();
+        CheckedGuard {
+            magic: self.magic,
+            crc: self.crc,
+            buff: self.buff,
+        }
+    }
+
+    /// Check if the buffer is valid, if not call the provided closure.
+    /// Then mark the buffer as valid and initialize it to a known state.
+    /// Returns a [`CheckedGuard`] so further mutations keep the CRC in sync on drop.
+    pub fn take_validate<F>(mut self, f: F) -> CheckedGuard<'static>
+    where
+        F: FnOnce(&mut [u8]),
+    {
+        if !self.valid() {
+            f(self.buff)
+        }
+        self.mark();
+        CheckedGuard {
+            magic: self.magic,
+            crc: self.crc,
+            buff: self.buff,
+        }
+    }
+
+    /// Get the buffer if the data and its CRC are valid, if not, return None.
+    pub fn get(&mut self) -> Option<&mut [u8]> {
+        if self.valid() {
+            return Some(self.buff);
+        } else {
+            return None;
+        }
+    }
+
+    /// Force reset the buffer to a known state via the closure, mark as valid (refreshing the CRC)
+    /// and return a [`CheckedGuard`] over the buffer.
+    pub fn reset<F>(&mut self, f: F) -> CheckedGuard<'_>
+    where
+        F: FnOnce(&mut [u8]),
+    {
+        f(self.buff);
+        self.mark();
+        CheckedGuard {
+            magic: self.magic,
+            crc: self.crc,
+            buff: self.buff,
+        }
+    }
+
+    /// Check if the buffer is valid, if not call the provided closure.
+    /// Then mark the buffer as valid and return a [`CheckedGuard`] that refreshes the CRC on drop.
+    pub fn validate_checked<F>(&mut self, f: F) -> CheckedGuard<'_>
+    where
+        F: FnOnce(&mut [u8]),
+    {
+        if !self.valid() {
+            f(self.buff)
+        }
+        self.mark();
+        CheckedGuard {
+            magic: self.magic,
+            crc: self.crc,
+            buff: self.buff,
+        }
+    }
+
     /// Mark the buffer as invalid
     pub fn invalidate(&mut self) {
         self.unmark();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leak_buff(len: usize) -> (*mut u8, usize) {
+        let leaked = Box::leak(vec![0u8; len].into_boxed_slice());
+        (leaked.as_mut_ptr(), len)
+    }
+
+    /// Reconstruct the `&'static mut [u8]` backing a buffer leaked by `leak_buff`, so a
+    /// test can observe bytes written through a `PersistentBuff` that has since been
+    /// consumed (e.g. by [`PersistentBuff::take_zeroize`]).
+    unsafe fn reclaim(ptr: *mut u8, len: usize) -> &'static mut [u8] {
+        core::slice::from_raw_parts_mut(ptr, len)
+    }
+
+    fn managed_from_buff(b: &'static mut [u8]) -> PersistentBuff {
+        PersistentBuff {
+            magic: b.as_mut_ptr().cast::<u32>(),
+            buff: &mut b[core::mem::size_of::<u32>()..],
+        }
+    }
+
+    // `take_raw`/`take_managed` themselves need the linker-provided
+    // `_persistent_buff_{start,end}` symbols, which only exist with a real
+    // linker script, so a hosted test can't drive them directly. Exercise
+    // the `try_claim` primitive they're both built on instead.
+    //
+    // Note this doesn't actually cover the `thread-safe` feature's
+    // `critical_section` wiring: a bare `AtomicBool::swap` is already atomic
+    // on the host, so this test passes identically whether or not that
+    // synchronization strategy is wired in correctly. See
+    // `critical_section_serializes_concurrent_access` below for a test that
+    // would actually fail if that wiring were broken.
+    #[test]
+    fn concurrent_claim_yields_exactly_one_true() {
+        static FLAG: AtomicBool = AtomicBool::new(false);
+
+        let results = std::thread::scope(|s| {
+            let a = s.spawn(|| try_claim(&FLAG));
+            let b = s.spawn(|| try_claim(&FLAG));
+            [a.join().unwrap(), b.join().unwrap()]
+        });
+
+        assert_eq!(results.iter().filter(|&&claimed| claimed).count(), 1);
+    }
+
+    // Unlike `concurrent_claim_yields_exactly_one_true`, this drives threads through
+    // `critical_section` directly and holds each section open long enough (well past
+    // any thread-spawn jitter) that, if it didn't genuinely serialize access, two
+    // threads would reliably be observed inside at once. A bare atomic counter without
+    // the sleep wouldn't do this reliably: a lost increment from unsynchronized access
+    // is a narrow race that may not reproduce on a given run, whereas holding the
+    // section open deterministically widens the overlap window a broken
+    // `critical_section` would fail to prevent. Requires the `critical-section`
+    // crate's `std` feature (its real mutex-based implementation) as a
+    // dev-dependency, so there's a registered implementation to run against on host.
+    #[cfg(feature = "thread-safe")]
+    #[test]
+    fn critical_section_serializes_concurrent_access() {
+        use core::sync::atomic::AtomicUsize;
+
+        static INSIDE: AtomicUsize = AtomicUsize::new(0);
+        static MAX_INSIDE: AtomicUsize = AtomicUsize::new(0);
+
+        const THREADS: usize = 4;
+        const ITERATIONS: usize = 20;
+
+        std::thread::scope(|s| {
+            for _ in 0..THREADS {
+                s.spawn(|| {
+                    for _ in 0..ITERATIONS {
+                        critical_section(|| {
+                            let now = INSIDE.fetch_add(1, Ordering::SeqCst) + 1;
+                            MAX_INSIDE.fetch_max(now, Ordering::SeqCst);
+                            std::thread::sleep(std::time::Duration::from_millis(1));
+                            INSIDE.fetch_sub(1, Ordering::SeqCst);
+                        });
+                    }
+                });
+            }
+        });
+
+        assert_eq!(MAX_INSIDE.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn persistent_buff_checked_from_raw_rejects_region_too_small_for_header() {
+        let (ptr, len) = leak_buff(2 * core::mem::size_of::<u32>() - 1);
+        assert!(PersistentBuffChecked::from_raw(unsafe { reclaim(ptr, len) }).is_none());
+    }
+
+    #[test]
+    fn take_zeroize_scrubs_the_payload() {
+        let (ptr, len) = leak_buff(32);
+        let mut managed = managed_from_buff(unsafe { reclaim(ptr, len) });
+        managed.reset(|b| b.fill(0xAA));
+
+        managed.take_zeroize();
+
+        let raw = unsafe { reclaim(ptr, len) };
+        assert!(raw[core::mem::size_of::<u32>()..].iter().all(|&b| b == 0));
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn invalidate_scrubs_the_payload_under_zeroize_feature() {
+        let (ptr, len) = leak_buff(32);
+        let mut managed = managed_from_buff(unsafe { reclaim(ptr, len) });
+        managed.reset(|b| b.fill(0xAA));
+
+        managed.invalidate();
+
+        assert!(!managed.valid());
+        assert!(managed.buff.iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn validate_scrubbed_zeroes_stale_bytes_before_first_init() {
+        let (ptr, len) = leak_buff(32);
+        let mut managed = managed_from_buff(unsafe { reclaim(ptr, len) });
+        // Simulate residual RAM contents left over from a previous owner of the region.
+        managed.buff.fill(0xAA);
+        assert!(!managed.valid());
+
+        let mut seen_by_init = None;
+        managed.validate_scrubbed(|b| seen_by_init = Some(b.to_vec()));
+
+        assert_eq!(
+            seen_by_init.unwrap(),
+            vec![0u8; 32 - core::mem::size_of::<u32>()]
+        );
+        assert!(managed.valid());
+    }
+}
@@ -0,0 +1,310 @@
+//! Splitting the persistent region into several independently-validated sub-buffers.
+
+use crate::crc::{crc32, crc32_finalize, crc32_init, crc32_update};
+use crate::{PersistentBuff, MAGIC_NUMBER};
+
+/// Bytes reserved before a partition's data: magic, CRC32 and length, in that order.
+const PARTITION_HEADER_LEN: usize = 3 * core::mem::size_of::<u32>();
+
+/// Bytes reserved at the very front of the region for the crate-level layout header:
+/// a magic tag plus a CRC32 fingerprint of the `specs` partitions were last carved with.
+const LAYOUT_HEADER_LEN: usize = 2 * core::mem::size_of::<u32>();
+
+/// Magic value stamped on the layout header, distinct from [`MAGIC_NUMBER`] so a region
+/// that has never been carved into partitions can't be mistaken for one whose layout
+/// fingerprint happens to read back as zero.
+const LAYOUT_MAGIC: u32 = MAGIC_NUMBER ^ 0xFFFF_FFFF;
+
+/// Fold `specs` into a single CRC32 fingerprint, so adding, removing, resizing,
+/// reordering or renaming a partition yields a different value.
+fn layout_fingerprint<const N: usize>(specs: &[(&'static str, usize); N]) -> u32 {
+    let mut state = crc32_init();
+    for (name, len) in specs {
+        state = crc32_update(state, name.as_bytes());
+        state = crc32_update(state, &(*len as u32).to_le_bytes());
+    }
+    crc32_finalize(state)
+}
+
+/// Re-slice `*slice` in place, handing back its first `n` bytes.
+///
+/// Lets [`PersistentPartitions::take`] carve a single `&'static mut [u8]` into
+/// consecutive chunks without ever holding two live references to the same bytes.
+fn split_off_front(slice: &mut &'static mut [u8], n: usize) -> &'static mut [u8] {
+    let full = core::mem::take(slice);
+    let (head, tail) = full.split_at_mut(n);
+    *slice = tail;
+    head
+}
+
+/// One named, independently validated sub-buffer carved out of a [`PersistentPartitions`] region.
+///
+/// Like [`crate::PersistentBuffChecked`], it stores a magic tag and a CRC32 of its
+/// payload, plus the length it was created with, so corruption of one partition's own
+/// data doesn't invalidate the others. A change to the *declared* `specs` is a
+/// different matter: see [`PersistentPartitions`] for why that invalidates every
+/// partition in the set together instead.
+pub struct PersistentPartition {
+    name: &'static str,
+    magic: *mut u32,
+    crc: *mut u32,
+    len: *mut u32,
+    buff: &'static mut [u8],
+}
+
+impl PersistentPartition {
+    fn from_chunk(name: &'static str, chunk: &'static mut [u8]) -> Self {
+        let magic = chunk.as_mut_ptr().cast::<u32>();
+        // SAFETY: `chunk` is at least `PARTITION_HEADER_LEN` bytes long, enforced by
+        // `PersistentPartitions::take`.
+        let crc = unsafe { magic.add(1) };
+        let len = unsafe { magic.add(2) };
+        Self {
+            name,
+            magic,
+            crc,
+            len,
+            buff: &mut chunk[PARTITION_HEADER_LEN..],
+        }
+    }
+
+    /// The name this partition was declared with.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Mark the partition valid, stamping its magic, length and CRC32.
+    fn mark(&mut self) {
+        unsafe {
+            self.len.write_unaligned(self.buff.len() as u32);
+            self.crc.write_unaligned(crc32(self.buff));
+            self.magic.write_unaligned(MAGIC_NUMBER);
+        }
+    }
+
+    /// Mark the partition as invalid.
+    fn unmark(&mut self) {
+        unsafe {
+            self.magic.write_unaligned(0);
+        }
+    }
+
+    /// Verify if the partition is valid: its magic is set, its recorded length matches
+    /// the data slice it was carved with, and its CRC32 matches the current payload.
+    pub fn valid(&self) -> bool {
+        unsafe {
+            self.magic.read_unaligned() == MAGIC_NUMBER
+                && self.len.read_unaligned() as usize == self.buff.len()
+                && self.crc.read_unaligned() == crc32(self.buff)
+        }
+    }
+
+    /// Get the partition's payload if valid, if not, return None.
+    pub fn get(&mut self) -> Option<&mut [u8]> {
+        if self.valid() {
+            return Some(self.buff);
+        } else {
+            return None;
+        }
+    }
+
+    /// Check if the partition is valid, if not call the provided closure.
+    /// Then mark the partition as valid.
+    pub fn validate<F>(&mut self, f: F) -> &mut [u8]
+    where
+        F: FnOnce(&mut [u8]),
+    {
+        if !self.valid() {
+            f(self.buff)
+        }
+        self.mark();
+        self.buff
+    }
+
+    /// Force reset the partition to a known state via the closure and mark as valid.
+    pub fn reset<F>(&mut self, f: F) -> &mut [u8]
+    where
+        F: FnOnce(&mut [u8]),
+    {
+        f(self.buff);
+        self.mark();
+        self.buff
+    }
+
+    /// Mark the partition as invalid.
+    pub fn invalidate(&mut self) {
+        self.unmark();
+    }
+}
+
+/// Carves the raw persistent region (see [`PersistentBuff::take_raw`]) into several
+/// fixed-size, individually-tagged [`PersistentPartition`]s.
+///
+/// Partitions are laid out sequentially as `[magic|crc|len|data]` headers in the order
+/// they're declared, directly after a crate-level layout header recording a CRC32
+/// fingerprint of `specs`. Corruption of one partition's own data only invalidates that
+/// partition. But every partition's byte offset depends on the sizes of all the ones
+/// before it, so a change to `specs` itself (a partition added, removed, resized,
+/// reordered or renamed) would otherwise make every partition from that point on
+/// silently read from the wrong offset instead of failing its own check. The layout
+/// fingerprint catches that explicitly: any `specs` mismatch invalidates every
+/// partition in the set together, rather than risk misreading stale bytes as valid data
+/// at the wrong offset.
+pub struct PersistentPartitions<const N: usize> {
+    partitions: [PersistentPartition; N],
+}
+
+impl<const N: usize> PersistentPartitions<N> {
+    /// Take the raw persistent region and carve it into `N` partitions, named and
+    /// sized by `specs` in declaration order.
+    ///
+    /// Returns `None` if the region was already taken, or if it is too small to fit
+    /// the layout header plus every partition's header and data.
+    pub fn take(specs: [(&'static str, usize); N]) -> Option<Self> {
+        PersistentBuff::take_raw().and_then(|raw| Self::from_raw(raw, specs))
+    }
+
+    /// Carve an already-acquired raw persistent region, named and sized by `specs`.
+    /// See [Self::take].
+    ///
+    /// # Safety
+    /// Calling this function could allow to have two mutable reference to the same buffer.
+    /// Make sure to only have one reference at a time to avoid multiple mutable reference.
+    pub unsafe fn steal(specs: [(&'static str, usize); N]) -> Option<Self> {
+        Self::from_raw(PersistentBuff::steal(), specs)
+    }
+
+    fn from_raw(mut raw: &'static mut [u8], specs: [(&'static str, usize); N]) -> Option<Self> {
+        if raw.len() < LAYOUT_HEADER_LEN {
+            return None;
+        }
+        let header = split_off_front(&mut raw, LAYOUT_HEADER_LEN);
+        let layout_magic = header.as_mut_ptr().cast::<u32>();
+        // SAFETY: `header` is exactly `LAYOUT_HEADER_LEN` bytes, checked above.
+        let layout_fingerprint_ptr = unsafe { layout_magic.add(1) };
+        let expected_fingerprint = layout_fingerprint(&specs);
+        // SAFETY: both pointers were derived from `header` above.
+        let layout_matches = unsafe {
+            layout_magic.read_unaligned() == LAYOUT_MAGIC
+                && layout_fingerprint_ptr.read_unaligned() == expected_fingerprint
+        };
+
+        let mut partitions: [core::mem::MaybeUninit<PersistentPartition>; N] =
+            unsafe { core::mem::MaybeUninit::uninit().assume_init() };
+
+        for (slot, (name, len)) in partitions.iter_mut().zip(specs) {
+            let chunk_len = PARTITION_HEADER_LEN + len;
+            if raw.len() < chunk_len {
+                return None;
+            }
+            let chunk = split_off_front(&mut raw, chunk_len);
+            slot.write(PersistentPartition::from_chunk(name, chunk));
+        }
+
+        // SAFETY: the loop above wrote every slot, or this function already returned `None`.
+        let mut partitions =
+            unsafe { core::mem::transmute_copy::<_, [PersistentPartition; N]>(&partitions) };
+
+        if !layout_matches {
+            // `specs` doesn't match what this region was last carved with, so every
+            // partition may be reading from the wrong byte range entirely: don't trust
+            // any of their individual magic/CRC checks, and re-stamp the new layout.
+            for partition in partitions.iter_mut() {
+                partition.unmark();
+            }
+            unsafe {
+                layout_fingerprint_ptr.write_unaligned(expected_fingerprint);
+                layout_magic.write_unaligned(LAYOUT_MAGIC);
+            }
+        }
+
+        Some(Self { partitions })
+    }
+
+    /// Get a partition by name.
+    pub fn partition(&mut self, name: &str) -> Option<&mut PersistentPartition> {
+        self.partitions.iter_mut().find(|p| p.name == name)
+    }
+
+    /// Get a partition by its index in the declared spec order.
+    pub fn partition_at(&mut self, index: usize) -> Option<&mut PersistentPartition> {
+        self.partitions.get_mut(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region_len(specs: &[(&'static str, usize)]) -> usize {
+        LAYOUT_HEADER_LEN
+            + specs
+                .iter()
+                .map(|(_, len)| PARTITION_HEADER_LEN + len)
+                .sum::<usize>()
+    }
+
+    fn leak_buff(len: usize) -> (*mut u8, usize) {
+        let leaked = Box::leak(vec![0u8; len].into_boxed_slice());
+        (leaked.as_mut_ptr(), len)
+    }
+
+    /// Reconstruct the `&'static mut [u8]` backing a buffer leaked by `leak_buff`, so a
+    /// test can reopen the same bytes with a different `specs` to simulate a reboot.
+    unsafe fn reclaim(ptr: *mut u8, len: usize) -> &'static mut [u8] {
+        core::slice::from_raw_parts_mut(ptr, len)
+    }
+
+    #[test]
+    fn corrupting_one_partitions_data_does_not_invalidate_the_others() {
+        let specs = [("a", 8), ("b", 8)];
+        let (ptr, len) = leak_buff(region_len(&specs));
+        let mut partitions =
+            PersistentPartitions::from_raw(unsafe { reclaim(ptr, len) }, specs).unwrap();
+        partitions.partition("a").unwrap().mark();
+        partitions.partition("b").unwrap().mark();
+
+        partitions.partition("a").unwrap().get().unwrap()[0] ^= 0xFF;
+
+        assert!(!partitions.partition("a").unwrap().valid());
+        assert!(partitions.partition("b").unwrap().valid());
+    }
+
+    #[test]
+    fn resizing_one_partitions_spec_invalidates_the_whole_set() {
+        // Big enough for the grown layout used on the second `from_raw` below.
+        let (ptr, len) = leak_buff(region_len(&[("a", 16), ("b", 8)]));
+
+        {
+            let specs = [("a", 8), ("b", 8)];
+            let mut partitions =
+                PersistentPartitions::from_raw(unsafe { reclaim(ptr, len) }, specs).unwrap();
+            partitions.partition("a").unwrap().mark();
+            partitions.partition("b").unwrap().mark();
+        }
+
+        let grown_specs = [("a", 16), ("b", 8)];
+        let mut partitions =
+            PersistentPartitions::from_raw(unsafe { reclaim(ptr, len) }, grown_specs).unwrap();
+        assert!(!partitions.partition("a").unwrap().valid());
+        assert!(!partitions.partition("b").unwrap().valid());
+    }
+
+    #[test]
+    fn reopening_with_the_same_specs_keeps_partitions_valid() {
+        let (ptr, len) = leak_buff(region_len(&[("a", 8), ("b", 8)]));
+        let specs = [("a", 8), ("b", 8)];
+
+        {
+            let mut partitions =
+                PersistentPartitions::from_raw(unsafe { reclaim(ptr, len) }, specs).unwrap();
+            partitions.partition("a").unwrap().mark();
+            partitions.partition("b").unwrap().mark();
+        }
+
+        let mut partitions =
+            PersistentPartitions::from_raw(unsafe { reclaim(ptr, len) }, specs).unwrap();
+        assert!(partitions.partition("a").unwrap().valid());
+        assert!(partitions.partition("b").unwrap().valid());
+    }
+}
@@ -0,0 +1,26 @@
+//! Synchronization strategy selection.
+//!
+//! By default the crate assumes a single core with no preemption while the
+//! buffer is claimed, so the closure just runs inline. Enabling the
+//! `thread-safe` feature routes it through [`critical_section::with`]
+//! instead, so the claim is atomic across interrupts and cores too, at the
+//! cost of pulling in a `critical-section` implementation for the target.
+
+/// Run `f` with the synchronization strategy selected by the `thread-safe` feature.
+#[cfg(not(feature = "thread-safe"))]
+pub(crate) fn critical_section<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    f()
+}
+
+/// Run `f` inside a [`critical_section::with`] so the claim is atomic across
+/// interrupts and cores.
+#[cfg(feature = "thread-safe")]
+pub(crate) fn critical_section<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    ::critical_section::with(|_| f())
+}
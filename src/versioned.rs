@@ -0,0 +1,168 @@
+//! Schema-versioned persistent storage with an in-place migration hook.
+
+use crate::{PersistentBuff, MAGIC_NUMBER};
+
+/// Bytes reserved before a versioned buffer's data: magic and schema version.
+const VERSIONED_HEADER_LEN: usize = core::mem::size_of::<u32>() + core::mem::size_of::<u16>();
+
+/// A persistent buff tagged with a `u16` schema version, so a firmware update that
+/// changes the payload's meaning can migrate stale data in place instead of either
+/// silently reinterpreting it or forcing a full wipe.
+pub struct PersistentBuffVersioned {
+    magic: *mut u32,
+    version: *mut u16,
+    buff: &'static mut [u8],
+}
+
+impl PersistentBuffVersioned {
+    /// Take a managed, versioned view of the persistent buff.
+    ///
+    /// Returns `None` if the region was already taken, or if it is too small to hold
+    /// the magic and schema version header.
+    pub fn take_managed() -> Option<Self> {
+        PersistentBuff::take_raw().and_then(Self::from_raw)
+    }
+
+    /// Steal a managed, versioned view of the persistent buff without check.
+    /// See [Self::take_managed].
+    ///
+    /// # Safety
+    /// Calling this function could allow to have two mutable reference to the same buffer.
+    /// Make sure to only have one reference at a time to avoid multiple mutable reference.
+    pub unsafe fn steal_managed() -> Option<Self> {
+        Self::from_raw(PersistentBuff::steal())
+    }
+
+    fn from_raw(b: &'static mut [u8]) -> Option<Self> {
+        if b.len() < VERSIONED_HEADER_LEN {
+            return None;
+        }
+        let magic = b.as_mut_ptr().cast::<u32>();
+        // SAFETY: `b` is at least `VERSIONED_HEADER_LEN` bytes long, checked above.
+        let version = unsafe {
+            magic
+                .cast::<u8>()
+                .add(core::mem::size_of::<u32>())
+                .cast::<u16>()
+        };
+        Some(Self {
+            magic,
+            version,
+            buff: &mut b[VERSIONED_HEADER_LEN..],
+        })
+    }
+
+    /// Mark the buffer valid and stamp it with `version`.
+    fn mark(&mut self, version: u16) {
+        unsafe {
+            self.version.write_unaligned(version);
+            self.magic.write_unaligned(MAGIC_NUMBER);
+        }
+    }
+
+    /// Verify if the buffer has valid data in it, regardless of its schema version.
+    pub fn valid(&self) -> bool {
+        unsafe { self.magic.read_unaligned() == MAGIC_NUMBER }
+    }
+
+    /// The schema version the buffer was last marked with.
+    /// Meaningless if [`Self::valid`] is `false`.
+    pub fn version(&self) -> u16 {
+        unsafe { self.version.read_unaligned() }
+    }
+
+    /// Take the buffer, migrating in place if needed.
+    ///
+    /// If the buffer is already valid and stamped with `current_version`, the data is
+    /// used as-is. If it's valid but stamped with a different schema version, `migrate`
+    /// is called with the previously stored version and the raw payload, so it can
+    /// migrate old data in place; returning `true` accepts the migration and re-stamps
+    /// the buffer with `current_version`. If the buffer was never marked valid, there's
+    /// no old schema to migrate from, so `migrate` is never called with garbage bytes;
+    /// `init` runs instead. `init` also runs as the fallback when `migrate` returns
+    /// `false`, mirroring the init path [`PersistentBuff::take_validate`] uses for an
+    /// invalid buffer.
+    pub fn take_versioned<M, I>(
+        mut self,
+        current_version: u16,
+        migrate: M,
+        init: I,
+    ) -> &'static mut [u8]
+    where
+        M: FnOnce(u16, &mut [u8]) -> bool,
+        I: FnOnce(&mut [u8]),
+    {
+        if self.valid() {
+            let old_version = self.version();
+            if old_version == current_version {
+                return self.buff;
+            }
+            if !migrate(old_version, self.buff) {
+                init(self.buff);
+            }
+        } else {
+            init(self.buff);
+        }
+        self.mark(current_version);
+        self.buff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leak_buff(len: usize) -> &'static mut [u8] {
+        Box::leak(vec![0u8; len].into_boxed_slice())
+    }
+
+    #[test]
+    fn region_too_small_for_header_returns_none_instead_of_panicking() {
+        assert!(PersistentBuffVersioned::from_raw(leak_buff(VERSIONED_HEADER_LEN - 1)).is_none());
+    }
+
+    #[test]
+    fn never_valid_buffer_skips_migrate_and_calls_init() {
+        let buff = PersistentBuffVersioned::from_raw(leak_buff(64)).unwrap();
+        let mut migrate_called = false;
+        let mut init_called = false;
+        let data = buff.take_versioned(
+            3,
+            |_, _| {
+                migrate_called = true;
+                true
+            },
+            |_| init_called = true,
+        );
+        assert!(!migrate_called);
+        assert!(init_called);
+        assert_eq!(data.len(), 64 - VERSIONED_HEADER_LEN);
+    }
+
+    #[test]
+    fn version_mismatch_on_valid_buffer_calls_migrate_with_old_version() {
+        let mut buff = PersistentBuffVersioned::from_raw(leak_buff(64)).unwrap();
+        buff.mark(1);
+        let mut seen_old_version = None;
+        buff.take_versioned(
+            2,
+            |old, _| {
+                seen_old_version = Some(old);
+                true
+            },
+            |_| panic!("init should not run when migrate accepts"),
+        );
+        assert_eq!(seen_old_version, Some(1));
+    }
+
+    #[test]
+    fn matching_version_returns_data_without_calling_either_closure() {
+        let mut buff = PersistentBuffVersioned::from_raw(leak_buff(64)).unwrap();
+        buff.mark(5);
+        buff.take_versioned(
+            5,
+            |_, _| panic!("migrate should not run when the version already matches"),
+            |_| panic!("init should not run when the version already matches"),
+        );
+    }
+}